@@ -0,0 +1,94 @@
+//! Async counterpart of [`BodyBytes`](../struct.BodyBytes.html), for stream types that
+//! implement `futures::io::AsyncRead` instead of the blocking `std::io::Read`.
+//!
+//! Gated behind the `async` feature so that pulling in `futures` stays opt-in for
+//! callers who only need the blocking iterator.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::io::AsyncRead;
+use futures::stream::Stream;
+
+/// `Stream` over bytes read from an async stream using a slice buffer.
+///
+/// This mirrors [`BodyBytes`](struct.BodyBytes.html)'s buffer-reuse strategy: any
+/// bytes already sitting in the buffer are emitted first, then the buffer is refilled
+/// by polling the underlying reader and its bytes are emitted in turn, all without any
+/// extra allocation.
+pub struct AsyncBodyBytes<'a, R> {
+    /// Underlying async stream to buffer and read bytes from.
+    stream: R,
+    /// Buffer for writing chunks into and reading bytes out of.
+    buf: &'a mut [u8],
+    /// Byte position in buffer to read next.
+    pos: usize,
+    /// Total number of valid bytes in buffer.
+    len: usize,
+}
+
+impl<'a, R: AsyncRead + Unpin> AsyncBodyBytes<'a, R> {
+    /// Create a new `AsyncBodyBytes` to poll-read chunks from the given stream into
+    /// the given buffer and yield the bytes in each chunk.
+    ///
+    /// Before polling the first chunk from the stream, any remaining bytes in the
+    /// given buffer are yielded starting at the given position out of the given
+    /// length, just as with [`BodyBytes::new`](struct.BodyBytes.html#method.new).
+    pub fn new(stream: R, buf: &'a mut [u8], start: usize, len: usize) -> Self {
+        AsyncBodyBytes {
+            stream,
+            buf,
+            pos: start,
+            len,
+        }
+    }
+}
+
+impl<'a, R: AsyncRead + Unpin> Stream for AsyncBodyBytes<'a, R> {
+    type Item = io::Result<u8>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.pos == this.len {
+            let len = match Pin::new(&mut this.stream).poll_read(cx, this.buf) {
+                Poll::Ready(Ok(len)) => len,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if len == 0 {
+                return Poll::Ready(None);
+            }
+
+            this.pos = 0;
+            this.len = len;
+        }
+
+        let b = this.buf[this.pos];
+        this.pos += 1;
+
+        Poll::Ready(Some(Ok(b)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on_stream;
+    use futures::io::Cursor;
+
+    #[test]
+    fn test_async_body_bytes() {
+        let stream = b"dy text";
+        let mut buf = [b'#'; 25];
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+
+        let r = AsyncBodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 18, 25);
+
+        let out: Vec<u8> = block_on_stream(r).map(|b| b.unwrap()).collect();
+
+        assert_eq!(&out[..], &b"some body text"[..]);
+    }
+}
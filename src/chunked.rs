@@ -0,0 +1,441 @@
+//! Iterator over the bytes in an HTTP request body encoded with
+//! `Transfer-Encoding: chunked`.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Safety limits enforced while decoding a chunked body, guarding against the
+/// oversized or ambiguous framing behind HTTP request-smuggling bugs.
+///
+/// These bound values that are otherwise attacker-controlled: a chunk-size value that
+/// overflows or dwarfs any real payload, and a chunk-header line that never finds its
+/// terminating CRLF.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct Limits {
+    /// Largest chunk size, in bytes, that will be accepted. A chunk-size header
+    /// decoding to more than this is rejected rather than believed.
+    pub max_chunk_size: usize,
+    /// Longest a chunk-size header line (including any extension) or trailer header
+    /// line is allowed to be, in bytes, before its terminating CRLF. Bounds how much
+    /// unterminated input will be buffered while looking for CRLF.
+    pub max_header_len: usize,
+}
+
+impl Limits {
+    /// Create a new set of limits.
+    pub fn new(max_chunk_size: usize, max_header_len: usize) -> Self {
+        Limits {
+            max_chunk_size,
+            max_header_len,
+        }
+    }
+}
+
+impl Default for Limits {
+    /// Limit chunks to 8 MiB and header/trailer lines to 8 KiB, which comfortably
+    /// covers legitimate traffic while still bounding attacker-controlled input.
+    fn default() -> Self {
+        Limits::new(8 * 1024 * 1024, 8 * 1024)
+    }
+}
+
+/// Why decoding a chunked body was rejected, as a distinct error kind a server can use
+/// to respond `400 Bad Request` rather than let the stream desync.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum ChunkedBodyError {
+    /// A chunk-size header decoded to a value over the configured
+    /// [`Limits::max_chunk_size`](struct.Limits.html#structfield.max_chunk_size), or
+    /// overflowed `usize` while decoding.
+    ChunkTooLarge,
+    /// A chunk-size or trailer header line ran past the configured
+    /// [`Limits::max_header_len`](struct.Limits.html#structfield.max_header_len)
+    /// without finding its terminating CRLF.
+    HeaderTooLong,
+    /// A chunk-size header was empty or contained a byte that isn't a hex digit, `;`,
+    /// or `\r`, such as a leading `+`, whitespace, or a `0x` prefix; or a `\r` in a
+    /// chunk-size or trailer header line wasn't followed by `\n`.
+    MalformedChunkSize,
+    /// The `\r\n` terminating a chunk's payload was missing or malformed.
+    MalformedChunkTerminator,
+}
+
+impl fmt::Display for ChunkedBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match *self {
+            ChunkedBodyError::ChunkTooLarge => "chunk size exceeds configured limit",
+            ChunkedBodyError::HeaderTooLong => "chunk header line exceeds configured limit",
+            ChunkedBodyError::MalformedChunkSize => "malformed chunk size",
+            ChunkedBodyError::MalformedChunkTerminator => "malformed chunk terminator",
+        };
+
+        f.write_str(msg)
+    }
+}
+
+impl Error for ChunkedBodyError {}
+
+/// Wrap a `ChunkedBodyError` as an `io::Error` of kind `InvalidData`.
+fn framing_error(err: ChunkedBodyError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Where `ChunkedBodyBytes` currently is within the chunked-encoding grammar.
+#[derive(PartialEq, Eq, Hash, Debug)]
+enum State {
+    /// Reading the `<hex-size>\r\n` header of the next chunk.
+    ChunkSize,
+    /// Yielding the payload of the current chunk; holds the number of payload bytes
+    /// left to yield. Once it reaches zero, the trailing `\r\n` after the payload
+    /// still needs to be consumed before the next chunk-size header.
+    ChunkData(usize),
+    /// The last chunk (`0\r\n`) was seen; skipping trailer headers up to the final
+    /// blank line.
+    Trailer,
+    /// The body is fully decoded; no further bytes will be yielded.
+    Done,
+}
+
+/// Iterator over the decoded payload bytes of a `Transfer-Encoding: chunked` body,
+/// reading chunks from a stream using a slice buffer.
+///
+/// This mirrors [`BodyBytes`](struct.BodyBytes.html) in how it reuses the given buffer
+/// across reads from the stream, but instead of yielding raw bytes it parses and
+/// strips the chunk-size headers, chunk-trailing CRLFs, and final trailer, yielding
+/// only the decoded payload bytes. It stops once the terminating `0\r\n` chunk and its
+/// trailer have been consumed.
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub struct ChunkedBodyBytes<'a, R: Read> {
+    /// Underlying stream to buffer and read bytes from.
+    stream: R,
+    /// Buffer for writing TCP chunks into and reading bytes out of.
+    buf: &'a mut [u8],
+    /// Byte position in buffer to read next.
+    pos: usize,
+    /// Total number of valid bytes in buffer.
+    len: usize,
+    /// Current position within the chunked-encoding grammar.
+    state: State,
+    /// Safety limits enforced while decoding.
+    limits: Limits,
+}
+
+impl<'a, R: Read> ChunkedBodyBytes<'a, R> {
+    /// Create a new `ChunkedBodyBytes` to decode a chunked body read from the given
+    /// stream into the given buffer, enforcing the default [`Limits`](struct.Limits.html).
+    ///
+    /// Before reading the first chunk from the stream, any remaining bytes in the
+    /// given buffer are parsed starting at the given position out of the given
+    /// length, just as with [`BodyBytes::new`](struct.BodyBytes.html#method.new).
+    pub fn new(stream: R, buf: &'a mut [u8], start: usize, len: usize) -> Self {
+        Self::with_limits(stream, buf, start, len, Limits::default())
+    }
+
+    /// Create a new `ChunkedBodyBytes` that rejects framing violating the given
+    /// [`Limits`](struct.Limits.html), instead of the defaults used by
+    /// [`new`](#method.new).
+    pub fn with_limits(stream: R, buf: &'a mut [u8], start: usize, len: usize,
+                        limits: Limits) -> Self {
+        ChunkedBodyBytes {
+            buf,
+            stream,
+            pos: start,
+            len,
+            state: State::ChunkSize,
+            limits,
+        }
+    }
+
+    /// Read the next raw byte out of the buffer, refilling from the stream when
+    /// exhausted, without interpreting the chunked framing.
+    fn read_raw(&mut self) -> io::Result<Option<u8>> {
+        if self.pos == self.len {
+            let len = self.stream.read(self.buf)?;
+
+            if len == 0 {
+                return Ok(None);
+            }
+
+            self.pos = 0;
+            self.len = len;
+        }
+
+        let b = self.buf[self.pos];
+        self.pos += 1;
+
+        Ok(Some(b))
+    }
+
+    /// Read the next raw byte, treating stream EOF as a framing error.
+    fn read_framed(&mut self) -> io::Result<u8> {
+        self.read_raw()?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected EOF in chunked body")
+        })
+    }
+
+    /// Consume a `\r\n` pair, erroring if either byte doesn't match.
+    fn consume_crlf(&mut self) -> io::Result<()> {
+        if self.read_framed()? != b'\r' {
+            return Err(framing_error(ChunkedBodyError::MalformedChunkTerminator));
+        }
+
+        if self.read_framed()? != b'\n' {
+            return Err(framing_error(ChunkedBodyError::MalformedChunkTerminator));
+        }
+
+        Ok(())
+    }
+
+    /// Skip the rest of a header line, returning the number of bytes skipped before
+    /// the terminating `\r\n`. `already` is the number of bytes already counted
+    /// against `self.limits.max_header_len` earlier in the same line (0 for a
+    /// trailer line, which starts fresh), so the whole line is bounded by a single
+    /// budget. Errors if the line runs past the limit without finding a `\r\n`.
+    fn skip_line(&mut self, already: usize) -> io::Result<usize> {
+        let mut skipped = already;
+
+        loop {
+            match self.read_framed()? {
+                b'\r' => {
+                    if self.read_framed()? != b'\n' {
+                        return Err(framing_error(ChunkedBodyError::MalformedChunkSize));
+                    }
+
+                    return Ok(skipped - already);
+                },
+                _ => {
+                    skipped += 1;
+
+                    if skipped > self.limits.max_header_len {
+                        return Err(framing_error(ChunkedBodyError::HeaderTooLong));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Parse a `<hex-size>[;ext]\r\n` chunk-size header and return the decoded size.
+    ///
+    /// Rejects a size over `self.limits.max_chunk_size`, a header line over
+    /// `self.limits.max_header_len`, and anything other than a strict run of hex
+    /// digits before the `;` or `\r` (so a leading `+`, whitespace, or `0x` prefix is
+    /// rejected rather than tolerated).
+    fn parse_chunk_size(&mut self) -> io::Result<usize> {
+        let mut size: usize = 0;
+        let mut saw_digit = false;
+        let mut header_len = 0;
+
+        loop {
+            let b = self.read_framed()?;
+
+            if b != b'\r' {
+                header_len += 1;
+
+                if header_len > self.limits.max_header_len {
+                    return Err(framing_error(ChunkedBodyError::HeaderTooLong));
+                }
+            }
+
+            match b {
+                b @ b'0'..=b'9' | b @ b'a'..=b'f' | b @ b'A'..=b'F' => {
+                    saw_digit = true;
+
+                    let digit = (b as char).to_digit(16).unwrap() as usize;
+
+                    size = size.checked_mul(16)
+                        .and_then(|s| s.checked_add(digit))
+                        .filter(|&s| s <= self.limits.max_chunk_size)
+                        .ok_or_else(|| framing_error(ChunkedBodyError::ChunkTooLarge))?;
+                },
+                b'\r' => {
+                    if !saw_digit {
+                        return Err(framing_error(ChunkedBodyError::MalformedChunkSize));
+                    }
+
+                    if self.read_framed()? != b'\n' {
+                        return Err(framing_error(ChunkedBodyError::MalformedChunkSize));
+                    }
+
+                    return Ok(size);
+                },
+                b';' => {
+                    if !saw_digit {
+                        return Err(framing_error(ChunkedBodyError::MalformedChunkSize));
+                    }
+
+                    self.skip_line(header_len)?;
+                    return Ok(size);
+                },
+                _ => return Err(framing_error(ChunkedBodyError::MalformedChunkSize)),
+            }
+        }
+    }
+}
+
+impl<'a, R: Read> Iterator for ChunkedBodyBytes<'a, R> {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.state {
+                State::Done => return None,
+                State::ChunkSize => {
+                    self.state = match self.parse_chunk_size() {
+                        Ok(0) => State::Trailer,
+                        Ok(n) => State::ChunkData(n),
+                        Err(e) => {
+                            self.state = State::Done;
+                            return Some(Err(e));
+                        },
+                    };
+                },
+                State::ChunkData(0) => {
+                    match self.consume_crlf() {
+                        Ok(()) => self.state = State::ChunkSize,
+                        Err(e) => {
+                            self.state = State::Done;
+                            return Some(Err(e));
+                        },
+                    }
+                },
+                State::ChunkData(n) => {
+                    return match self.read_framed() {
+                        Ok(b) => {
+                            self.state = State::ChunkData(n - 1);
+                            Some(Ok(b))
+                        },
+                        Err(e) => {
+                            self.state = State::Done;
+                            Some(Err(e))
+                        },
+                    };
+                },
+                State::Trailer => {
+                    match self.skip_line(0) {
+                        Ok(0) => self.state = State::Done,
+                        Ok(_) => {},
+                        Err(e) => {
+                            self.state = State::Done;
+                            return Some(Err(e));
+                        },
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_chunked_body_bytes() {
+        let body = b"4\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n";
+        let mut buf = [0; 64];
+
+        let mut r = ChunkedBodyBytes::new(Cursor::new(&body[..]), &mut buf[..], 0, 0);
+
+        let mut out = Vec::new();
+        for b in &mut r {
+            out.push(b.unwrap());
+        }
+
+        assert_eq!(&out[..], &b"Wikipedia in\r\n\r\nchunks."[..]);
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_with_trailer() {
+        let body = b"3\r\nfoo\r\n0\r\nX-Trailer: bar\r\n\r\n";
+        let mut buf = [0; 8];
+
+        let mut r = ChunkedBodyBytes::new(Cursor::new(&body[..]), &mut buf[..], 0, 0);
+
+        let mut out = Vec::new();
+        for b in &mut r {
+            out.push(b.unwrap());
+        }
+
+        assert_eq!(&out[..], &b"foo"[..]);
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_malformed_size() {
+        let body = b"zz\r\nfoo\r\n0\r\n\r\n";
+        let mut buf = [0; 8];
+
+        let mut r = ChunkedBodyBytes::new(Cursor::new(&body[..]), &mut buf[..], 0, 0);
+
+        assert!(r.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_rejects_smuggling_prefixes() {
+        for body in &[&b"+4\r\nWiki\r\n0\r\n\r\n"[..], &b" 4\r\nWiki\r\n0\r\n\r\n"[..],
+                      &b"0x4\r\nWiki\r\n0\r\n\r\n"[..]] {
+            let mut buf = [0; 32];
+            let mut r = ChunkedBodyBytes::new(Cursor::new(*body), &mut buf[..], 0, 0);
+
+            assert!(r.next().unwrap().is_err());
+        }
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_max_chunk_size() {
+        let body = b"FFFFFFFF\r\n";
+        let mut buf = [0; 32];
+        let limits = Limits::new(0xff, 1024);
+
+        let mut r = ChunkedBodyBytes::with_limits(Cursor::new(&body[..]), &mut buf[..], 0, 0,
+                                                   limits);
+
+        let err = r.next().unwrap().unwrap_err();
+        assert_eq!(*err.get_ref().unwrap().downcast_ref::<ChunkedBodyError>().unwrap(),
+                   ChunkedBodyError::ChunkTooLarge);
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_max_header_len() {
+        let body = b"1111111111\r\n";
+        let mut buf = [0; 32];
+        let limits = Limits::new(usize::MAX, 4);
+
+        let mut r = ChunkedBodyBytes::with_limits(Cursor::new(&body[..]), &mut buf[..], 0, 0,
+                                                   limits);
+
+        let err = r.next().unwrap().unwrap_err();
+        assert_eq!(*err.get_ref().unwrap().downcast_ref::<ChunkedBodyError>().unwrap(),
+                   ChunkedBodyError::HeaderTooLong);
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_malformed_terminator() {
+        let body = b"3\r\nfooXX0\r\n\r\n";
+        let mut buf = [0; 32];
+
+        let mut r = ChunkedBodyBytes::new(Cursor::new(&body[..]), &mut buf[..], 0, 0);
+
+        assert_eq!(r.next().unwrap().unwrap(), b'f');
+        assert_eq!(r.next().unwrap().unwrap(), b'o');
+        assert_eq!(r.next().unwrap().unwrap(), b'o');
+
+        let err = r.next().unwrap().unwrap_err();
+        assert_eq!(*err.get_ref().unwrap().downcast_ref::<ChunkedBodyError>().unwrap(),
+                   ChunkedBodyError::MalformedChunkTerminator);
+    }
+
+    #[test]
+    fn test_chunked_body_bytes_max_header_len_covers_extension() {
+        let body = b"111;AAAAAAA\r\n";
+        let mut buf = [0; 32];
+        let limits = Limits::new(usize::MAX, 4);
+
+        let mut r = ChunkedBodyBytes::with_limits(Cursor::new(&body[..]), &mut buf[..], 0, 0,
+                                                   limits);
+
+        let err = r.next().unwrap().unwrap_err();
+        assert_eq!(*err.get_ref().unwrap().downcast_ref::<ChunkedBodyError>().unwrap(),
+                   ChunkedBodyError::HeaderTooLong);
+    }
+}
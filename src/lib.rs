@@ -44,6 +44,16 @@
 
 use std::io::Read;
 
+mod chunked;
+
+pub use chunked::{ChunkedBodyBytes, ChunkedBodyError, Limits};
+
+#[cfg(feature = "async")]
+mod async_body;
+
+#[cfg(feature = "async")]
+pub use async_body::AsyncBodyBytes;
+
 /// Iterator over bytes in a stream using a slice buffer.
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub struct BodyBytes<'a, R: Read> {
@@ -55,6 +65,11 @@ pub struct BodyBytes<'a, R: Read> {
     pos: usize,
     /// Total number of valid bytes in buffer.
     len: usize,
+    /// Number of body bytes left to yield before the body ends, or `None` if the body
+    /// runs until the stream is exhausted.
+    remaining: Option<usize>,
+    /// Total number of body bytes yielded so far.
+    bytes_read: usize,
 }
 
 impl<'a, R: Read> BodyBytes<'a, R> {
@@ -63,13 +78,111 @@ impl<'a, R: Read> BodyBytes<'a, R> {
     ///
     /// Before reading the first chunk from the stream, any remaining bytes in the given
     /// buffer are iterated over starting at the given position out of the given length.
+    ///
+    /// This form has no way to know where the body ends, so it keeps yielding bytes
+    /// until the stream reaches EOF. On a connection that's reused for further requests
+    /// (HTTP keep-alive or pipelining), prefer [`with_length`](#method.with_length)
+    /// instead so bytes belonging to the next request aren't consumed as part of this
+    /// body.
     pub fn new(stream: R, buf: &'a mut [u8], start: usize, len: usize) -> Self {
         BodyBytes {
-            buf: buf,
-            stream: stream,
+            buf,
+            stream,
             pos: start,
-            len: len,
+            len,
+            remaining: None,
+            bytes_read: 0,
+        }
+    }
+
+    /// Create a new `BodyBytes` that stops after yielding exactly `content_length`
+    /// body bytes, leaving any further buffered or unread stream bytes untouched.
+    ///
+    /// This is the mode to use when the body's length is known up front, such as from
+    /// a `Content-Length` header, so that bytes belonging to a subsequent pipelined
+    /// request aren't mistaken for part of this body. Once the iterator is exhausted,
+    /// any bytes left over in the buffer can be recovered with
+    /// [`remaining_buf`](#method.remaining_buf) to continue parsing the next request.
+    pub fn with_length(stream: R, buf: &'a mut [u8], start: usize, len: usize,
+                       content_length: usize) -> Self {
+        BodyBytes {
+            buf,
+            stream,
+            pos: start,
+            len,
+            remaining: Some(content_length),
+            bytes_read: 0,
+        }
+    }
+
+    /// Return the bytes left over in the buffer that haven't been yielded yet.
+    ///
+    /// This is only meaningful once the iterator has stopped producing bytes, such as
+    /// after a length-bounded body has yielded all of its bytes, and lets a caller
+    /// resume parsing from the unconsumed remainder of the buffer (for example, the
+    /// start of a pipelined request).
+    pub fn remaining_buf(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+
+    /// Return the total number of body bytes yielded by this iterator so far.
+    ///
+    /// This tracks application-level progress through the body, independent of how
+    /// many bytes have been read off the wire into the buffer, which is useful for
+    /// reporting upload progress or enforcing a maximum body size mid-stream.
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Return the next contiguous span of buffered body bytes, refilling the buffer
+    /// from the stream when it's exhausted, or `None` once the body ends.
+    ///
+    /// Unlike [`next`](#tymethod.next), which yields one byte at a time, this returns
+    /// the entire available span in one call, which avoids the per-byte bounds checks
+    /// of the `Iterator` impl for consumers that just want to `write_all` the body to
+    /// a file or feed a streaming parser that accepts slices. Byte-at-a-time callers
+    /// using the `Iterator` impl are unaffected. Both share the same buffer position,
+    /// length bound, and byte count, so a caller can read a few chunks and then switch
+    /// to `next()` for the rest.
+    pub fn next_chunk(&mut self) -> Option<std::io::Result<&[u8]>> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        if self.pos == self.len {
+            let len = match self.stream.read(self.buf) {
+                Ok(l) => l,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if len == 0 {
+                return if self.remaining.is_some() {
+                    Some(Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof,
+                        "stream ended before all Content-Length body bytes were read")))
+                } else {
+                    None
+                };
+            }
+
+            self.pos = 0;
+            self.len = len;
+        }
+
+        let end = match self.remaining {
+            Some(remaining) => std::cmp::min(self.len, self.pos.saturating_add(remaining)),
+            None => self.len,
+        };
+
+        let chunk = &self.buf[self.pos..end];
+
+        self.pos = end;
+        self.bytes_read += chunk.len();
+
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= chunk.len();
         }
+
+        Some(Ok(chunk))
     }
 }
 
@@ -77,6 +190,10 @@ impl<'a, R: Read> Iterator for BodyBytes<'a, R> {
     type Item = std::io::Result<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
         if self.pos == self.len {
             let len = match self.stream.read(self.buf) {
                 Ok(l) => l,
@@ -84,7 +201,12 @@ impl<'a, R: Read> Iterator for BodyBytes<'a, R> {
             };
 
             if len == 0 {
-                return None;
+                return if self.remaining.is_some() {
+                    Some(Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof,
+                        "stream ended before all Content-Length body bytes were read")))
+                } else {
+                    None
+                };
             }
 
             self.pos = 0;
@@ -93,6 +215,11 @@ impl<'a, R: Read> Iterator for BodyBytes<'a, R> {
 
         let b = self.buf[self.pos];
         self.pos += 1;
+        self.bytes_read += 1;
+
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
 
         Some(Ok(b))
     }
@@ -107,7 +234,7 @@ mod test {
     fn test_body_bytes() {
         let stream = b"dy text";
         let mut buf = [b'#'; 25];
-        (&mut buf[..]).copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
 
         let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 18, 25);
 
@@ -142,7 +269,7 @@ mod test {
 
         let stream = b"cdefgh";
         let mut buf = [b'#'; 4];
-        (&mut buf[..2]).copy_from_slice(b"ab");
+        buf[..2].copy_from_slice(b"ab");
 
         let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 0, 2);
 
@@ -158,7 +285,7 @@ mod test {
 
         let stream = b" text";
         let mut buf = [b'#'; 25];
-        (&mut buf[..22]).copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome");
+        buf[..22].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome");
 
         let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 18, 22);
 
@@ -175,7 +302,7 @@ mod test {
 
         let stream = b"efghijklm";
         let mut buf = [b'#'; 4];
-        (&mut buf[..]).copy_from_slice(b"abcd");
+        buf[..].copy_from_slice(b"abcd");
 
         let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 2, 4);
 
@@ -192,4 +319,109 @@ mod test {
         assert_eq!(r.next().unwrap().unwrap(), b'm');
         assert!(r.next().is_none());
     }
+
+    #[test]
+    fn test_body_bytes_with_length() {
+        let stream = b"dy text\r\nGET / HTTP/1.1\r\n\r\n";
+        let mut buf = [b'#'; 25];
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+
+        let mut r = BodyBytes::with_length(Cursor::new(&stream[..]), &mut buf[..], 18, 25, 9);
+
+        assert_eq!(r.next().unwrap().unwrap(), b's');
+        assert_eq!(r.next().unwrap().unwrap(), b'o');
+        assert_eq!(r.next().unwrap().unwrap(), b'm');
+        assert_eq!(r.next().unwrap().unwrap(), b'e');
+        assert_eq!(r.next().unwrap().unwrap(), b' ');
+        assert_eq!(r.next().unwrap().unwrap(), b'b');
+        assert_eq!(r.next().unwrap().unwrap(), b'o');
+        assert_eq!(r.next().unwrap().unwrap(), b'd');
+        assert_eq!(r.next().unwrap().unwrap(), b'y');
+        assert!(r.next().is_none());
+        assert_eq!(r.remaining_buf(), b" text\r\nGET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_body_bytes_read_count() {
+        let stream = b"dy text";
+        let mut buf = [b'#'; 25];
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+
+        let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 18, 25);
+
+        assert_eq!(r.bytes_read(), 0);
+
+        for i in 1..15 {
+            r.next().unwrap().unwrap();
+            assert_eq!(r.bytes_read(), i);
+        }
+
+        assert!(r.next().is_none());
+        assert_eq!(r.bytes_read(), 14);
+    }
+
+    #[test]
+    fn test_body_bytes_next_chunk() {
+        let stream = b"dy text";
+        let mut buf = [b'#'; 25];
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+
+        let mut r = BodyBytes::new(Cursor::new(&stream[..]), &mut buf[..], 18, 25);
+
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"some bo");
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"dy text");
+        assert!(r.next_chunk().is_none());
+        assert_eq!(r.bytes_read(), 14);
+    }
+
+    #[test]
+    fn test_body_bytes_next_chunk_with_length() {
+        let stream = b"dy text\r\nGET / HTTP/1.1\r\n\r\n";
+        let mut buf = [b'#'; 25];
+        buf[..].copy_from_slice(b"GET / HTTP/1.1\r\n\r\nsome bo");
+
+        let mut r = BodyBytes::with_length(Cursor::new(&stream[..]), &mut buf[..], 18, 25, 9);
+
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"some bo");
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"dy");
+        assert!(r.next_chunk().is_none());
+        assert_eq!(r.remaining_buf(), b" text\r\nGET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_body_bytes_next_chunk_huge_content_length_does_not_overflow() {
+        let stream = b"ignored";
+        let mut buf = [b'#'; 8];
+        buf[..4].copy_from_slice(b"abcd");
+
+        let mut r = BodyBytes::with_length(Cursor::new(&stream[..]), &mut buf[..], 0, 4,
+                                            usize::MAX - 2);
+
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn test_body_bytes_with_length_errors_on_early_eof() {
+        let stream = b"";
+        let mut buf = [b'#'; 8];
+        buf[..2].copy_from_slice(b"ab");
+
+        let mut r = BodyBytes::with_length(Cursor::new(&stream[..]), &mut buf[..], 0, 2, 9);
+
+        assert_eq!(r.next().unwrap().unwrap(), b'a');
+        assert_eq!(r.next().unwrap().unwrap(), b'b');
+        assert_eq!(r.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_body_bytes_next_chunk_with_length_errors_on_early_eof() {
+        let stream = b"";
+        let mut buf = [b'#'; 8];
+        buf[..2].copy_from_slice(b"ab");
+
+        let mut r = BodyBytes::with_length(Cursor::new(&stream[..]), &mut buf[..], 0, 2, 9);
+
+        assert_eq!(r.next_chunk().unwrap().unwrap(), b"ab");
+        assert_eq!(r.next_chunk().unwrap().unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
 }